@@ -0,0 +1,137 @@
+//! Layered, include-aware configuration parsing.
+//!
+//! This mirrors the model used by Mercurial's `hgrc` files: a config is a
+//! flat `[section] key = value` text format, parsed line by line, where an
+//! `%include <path>` directive splices another file's entries in at that
+//! exact point (as if they had been written there inline) and an
+//! `%unset <key>` directive removes a key previously set within the current
+//! section by a lower-priority layer (typically an included base preset).
+//!
+//! Because entries are applied strictly in the order they are encountered —
+//! with `%include` recursing before the rest of the including file is
+//! read — a key set after an `%include` always overrides the same key set
+//! by that include, and a site's own `execution_config` naturally overrides
+//! any base preset(s) it includes.
+
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+/// Maximum `%include` nesting depth, guarding against include cycles.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// A fully resolved layered configuration: every `[section] key = value`
+/// entry, with later layers (main file entries and later `%include`s)
+/// already applied over earlier ones, and every `%unset` key already
+/// removed.
+#[derive(Debug, Default)]
+pub(crate) struct LayeredConfig {
+    entries: HashMap<(String, String), String>,
+}
+
+impl LayeredConfig {
+    /// Parse `text`, resolving any `%include` directive relative to
+    /// `base_dir`.
+    pub(crate) fn parse(text: &str, base_dir: &Path) -> anyhow::Result<Self> {
+        let mut config = Self::default();
+        config.load_str(text, base_dir, 0)?;
+        Ok(config)
+    }
+
+    /// Look up a resolved key, returning `None` if no layer ever set it (or
+    /// if it was `%unset` without being redefined afterwards).
+    pub(crate) fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.entries
+            .get(&(section.to_owned(), key.to_owned()))
+            .map(String::as_str)
+    }
+
+    fn load_file(&mut self, path: &Path, depth: usize) -> anyhow::Result<()> {
+        if depth >= MAX_INCLUDE_DEPTH {
+            anyhow::bail!(
+                "%include nesting exceeds {} levels at {:?}",
+                MAX_INCLUDE_DEPTH,
+                path
+            );
+        }
+        let text = read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read included config {:?}: {}", path, e))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("/"));
+        self.load_str(&text, base_dir, depth)
+    }
+
+    fn load_str(&mut self, text: &str, base_dir: &Path, depth: usize) -> anyhow::Result<()> {
+        let mut section = String::new();
+        let mut current_key: Option<(String, String)> = None;
+
+        for raw_line in text.lines() {
+            if raw_line.starts_with([' ', '\t']) && !raw_line.trim().is_empty() {
+                // Indented continuation line: append to the value currently
+                // being built.
+                if let Some(key) = &current_key {
+                    let value = self.entries.entry(key.clone()).or_default();
+                    value.push('\n');
+                    value.push_str(raw_line.trim());
+                }
+                continue;
+            }
+
+            let line = raw_line.trim();
+            current_key = None;
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix("%include") {
+                let include_path = rest.trim();
+                if include_path.is_empty() {
+                    anyhow::bail!("%include directive is missing a path");
+                }
+                let resolved = resolve_include(base_dir, include_path);
+                self.load_file(&resolved, depth + 1)?;
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix("%unset") {
+                let key = rest.trim();
+                if key.is_empty() {
+                    anyhow::bail!("%unset directive is missing a key");
+                }
+                self.entries.remove(&(section.clone(), key.to_owned()));
+                continue;
+            }
+
+            if line.starts_with('[') {
+                let Some(name) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) else {
+                    anyhow::bail!("malformed section header: {:?}", line);
+                };
+                section = name.trim().to_owned();
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                anyhow::bail!(
+                    "expected `key = value`, %include, or %unset, got: {:?}",
+                    line
+                );
+            };
+            let key = key.trim().to_owned();
+            let value = value.trim().to_owned();
+            current_key = Some((section.clone(), key.clone()));
+            self.entries.insert((section.clone(), key), value);
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolve an `%include` path relative to the including file's directory,
+/// unless it is already absolute.
+fn resolve_include(base_dir: &Path, include_path: &str) -> PathBuf {
+    let path = Path::new(include_path);
+    if path.is_absolute() {
+        path.to_owned()
+    } else {
+        base_dir.join(path)
+    }
+}
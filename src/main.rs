@@ -11,16 +11,74 @@
 //! See the `LICENSE_MIT.markdown` file in the Veracruz root directory for
 //! information on licensing and copyright.
 
+mod config;
+#[cfg(test)]
+mod munger;
+
+use anyhow::Context;
 use darknet::{BBox, Detection, Image, Network};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::fmt::Write as _;
 use std::fs::{read_to_string, File};
 use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+
+/// Output serialization chosen via [`DarknetInferenceService::output_format`].
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(Serialize))]
+pub(crate) enum OutputFormat {
+    /// The original tab-separated, human-readable report.
+    Text,
+    /// A JSON array of [`DetectionRecord`], one per surviving detection.
+    Json,
+    /// A CSV table with the same columns as [`DetectionRecord`].
+    Csv,
+}
+
+/// One surviving detection, shaped for the `Json` and `Csv` output formats.
+/// Kept separate from the `Text` path so neither has to compromise: `Text`
+/// stays human-scannable with a rounded percentage, while `Json`/`Csv` carry
+/// the full-precision confidence for programmatic consumers.
+#[derive(Serialize, Debug)]
+struct DetectionRecord<'a> {
+    label: &'a str,
+    confidence: f32,
+    bbox: BBoxRecord,
+    class_index: usize,
+}
+
+/// [`BBox`] mirrored as a serializable struct, since `darknet::BBox` does not
+/// implement `serde::Serialize`.
+#[derive(Serialize, Debug)]
+struct BBoxRecord {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+impl From<BBox> for BBoxRecord {
+    fn from(BBox { x, y, w, h }: BBox) -> Self {
+        Self { x, y, w, h }
+    }
+}
+
+/// One input's surviving detections, keyed by the input's own path, for the
+/// aggregated `Json` batch report. Concatenating independent
+/// [`render_output`] calls (one complete `[...]` array per input) would
+/// produce back-to-back JSON documents that no parser can read as one file;
+/// wrapping each input's records under its path instead yields a single
+/// well-formed array no matter how many inputs contributed to it.
+#[derive(Serialize, Debug)]
+struct AggregatedDetections<'a> {
+    input: String,
+    detections: Vec<DetectionRecord<'a>>,
+}
 
 /// Module's API.
 #[derive(Deserialize, Debug)]
+#[cfg_attr(test, derive(Serialize))]
 pub(crate) struct DarknetInferenceService {
     /// Path to the input (image) to be fed to the network.
     input_path: PathBuf,
@@ -48,6 +106,272 @@ pub(crate) struct DarknetInferenceService {
     /// Whether the image should be letterboxed, i.e. padded while preserving
     /// its aspect ratio, or resized, before being fed to the model.
     letterbox: bool,
+    /// Optional path to write an annotated copy of the input image to, with
+    /// each surviving detection's bounding box and label drawn on top. No
+    /// image is produced when this is `None`.
+    annotated_output_path: Option<PathBuf>,
+    /// Additional inputs to run through the same loaded network as
+    /// `input_path`. When non-empty, `input_path` and every path in this
+    /// list are treated as one batch: the network and labels are loaded
+    /// once and `predict` is run once per image. Leave empty to run in the
+    /// original single-image mode.
+    batch_input_paths: Vec<PathBuf>,
+    /// When a batch is in use, write every image's detections into one
+    /// report at `output_path`, keyed by input path, instead of one
+    /// report file per input living under `output_path` treated as a
+    /// directory. Ignored in single-image mode.
+    aggregate_batch_output: bool,
+    /// Serialization used when writing the detection report.
+    output_format: OutputFormat,
+}
+
+/// Deterministic per-class colour assignment for the annotated output image.
+///
+/// Detections are drawn with a colour derived from their class index so that
+/// the same class is always rendered with the same colour across runs and
+/// images, without having to ship or configure a fixed palette.
+fn class_color(class_index: usize) -> (u8, u8, u8) {
+    // Spread classes evenly around the hue wheel and convert to RGB at fixed
+    // saturation/value, so colors stay visually distinct even for label sets
+    // with hundreds of classes.
+    let hue = (class_index as f32 * 137.508) % 360.0;
+    hsv_to_rgb(hue, 0.65, 0.95)
+}
+
+/// Minimal HSV -> RGB conversion, `h` in degrees, `s`/`v` in `[0.0, 1.0]`.
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (u8, u8, u8) {
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let m = v - c;
+    let (r, g, b) = match h as u32 {
+        0..=59 => (c, x, 0.0),
+        60..=119 => (x, c, 0.0),
+        120..=179 => (0.0, c, x),
+        180..=239 => (0.0, x, c),
+        240..=299 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    (
+        ((r + m) * 255.0) as u8,
+        ((g + m) * 255.0) as u8,
+        ((b + m) * 255.0) as u8,
+    )
+}
+
+/// Derive a per-input report (or annotated image) file name from an input
+/// path, for use when a batch is written as one file per input rather than
+/// a single aggregated report. Uses the input's path components (minus its
+/// own extension) joined with `_`, rather than just its file stem, so that
+/// inputs with the same stem in different directories (`/a/img.jpg`,
+/// `/b/img.jpg`) don't collide on the same output file. Only `Normal`
+/// components are kept — `RootDir`/prefix components are skipped so the
+/// result is always relative: call sites `join` it onto `output_path`/
+/// `annotated_output_path`, and `Path::join` discards the base entirely if
+/// given an absolute path.
+fn batch_file_name(input: &Path, extension: &str) -> PathBuf {
+    let sanitized = input
+        .with_extension("")
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("_");
+    Path::new(&sanitized).with_extension(extension)
+}
+
+/// Check that every detected class index is covered by the labels file,
+/// returning a typed error instead of letting a subsequent `object_labels[i]`
+/// index out of bounds. `labels_path` is only used to make the error
+/// actionable.
+fn validate_label_coverage(
+    labels_len: usize,
+    max_class_index: Option<usize>,
+    labels_path: &Path,
+) -> anyhow::Result<()> {
+    if let Some(max_class_index) = max_class_index {
+        if max_class_index >= labels_len {
+            anyhow::bail!(
+                "labels file {:?} has {} entries but the network produced detections for \
+                 class index {}; the labels file does not cover the network's class count",
+                labels_path,
+                labels_len,
+                max_class_index,
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Parse a layered-config threshold value, rejecting `NaN`/infinite
+/// spellings that `f32::from_str` otherwise accepts happily but that make no
+/// sense as a probability threshold.
+fn parse_threshold(field: &str, value: &str) -> anyhow::Result<f32> {
+    let parsed: f32 = value
+        .parse()
+        .with_context(|| format!("invalid {field}: {value:?}"))?;
+    if !parsed.is_finite() {
+        anyhow::bail!("{field} must be a finite number, got {:?}", value);
+    }
+    Ok(parsed)
+}
+
+/// Parse a layered-config boolean value, accepting the same spellings as
+/// Mercurial's `hgrc` (`true`/`false`, `yes`/`no`, `1`/`0`).
+fn parse_bool(value: &str) -> anyhow::Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "1" => Ok(true),
+        "false" | "no" | "0" => Ok(false),
+        other => anyhow::bail!("invalid boolean value: {:?}", other),
+    }
+}
+
+/// Quote a CSV field if it contains a comma, quote, or newline.
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// File extension conventionally associated with each [`OutputFormat`].
+fn output_extension(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Text => "txt",
+        OutputFormat::Json => "json",
+        OutputFormat::Csv => "csv",
+    }
+}
+
+/// One sorted, thresholded detection: its original index, the `Detection`
+/// itself, its best class' probability and index, and that class' label.
+type LabeledDetection<'a> = (usize, (Detection, f32, usize, &'a String));
+
+/// Serialize a set of labeled detections in the requested [`OutputFormat`].
+fn render_output(
+    format: OutputFormat,
+    labeled_detections: &[LabeledDetection],
+) -> anyhow::Result<String> {
+    match format {
+        OutputFormat::Text => {
+            let mut output = String::new();
+            for (_, (detection, prob, _, label)) in labeled_detections {
+                let BBox { x, y, w, h } = detection.bbox();
+                writeln!(
+                    output,
+                    "{}\t{:.2}%\tx: {}\ty: {}\tw: {}\th: {}",
+                    label,
+                    prob * 100.0,
+                    x,
+                    y,
+                    w,
+                    h,
+                )?
+            }
+            Ok(output)
+        }
+        OutputFormat::Json => {
+            let records: Vec<DetectionRecord> = labeled_detections
+                .iter()
+                .map(
+                    |(_, (detection, prob, class_index, label))| DetectionRecord {
+                        label,
+                        confidence: *prob,
+                        bbox: detection.bbox().into(),
+                        class_index: *class_index,
+                    },
+                )
+                .collect();
+            Ok(serde_json::to_string(&records)?)
+        }
+        OutputFormat::Csv => {
+            let mut output = String::from("label,confidence,x,y,w,h,class_index\n");
+            for (_, (detection, prob, class_index, label)) in labeled_detections {
+                let BBox { x, y, w, h } = detection.bbox();
+                writeln!(
+                    output,
+                    "{},{},{},{},{},{},{}",
+                    csv_escape(label),
+                    prob,
+                    x,
+                    y,
+                    w,
+                    h,
+                    class_index,
+                )?;
+            }
+            Ok(output)
+        }
+    }
+}
+
+/// Serialize every input's detections from a batch into one aggregated
+/// report, keyed by each input's own path, in the requested [`OutputFormat`].
+///
+/// Unlike [`render_output`], which renders one input's detections as a
+/// standalone document, this always produces a single well-formed document
+/// no matter how many inputs it covers: for `Json` that means one array of
+/// per-input objects rather than one `[...]` array per input concatenated
+/// after another, and for `Csv` that means a single header row followed by
+/// every input's rows tagged with an `input` column, rather than the header
+/// repeating once per input with no way to tell which input a row came from.
+fn render_aggregated_output(
+    format: OutputFormat,
+    entries: &[(&Path, &[LabeledDetection])],
+) -> anyhow::Result<String> {
+    match format {
+        OutputFormat::Text => {
+            let mut output = String::new();
+            for (input, labeled_detections) in entries {
+                writeln!(output, "# {}", input.display())?;
+                output.push_str(&render_output(OutputFormat::Text, labeled_detections)?);
+            }
+            Ok(output)
+        }
+        OutputFormat::Json => {
+            let aggregated: Vec<AggregatedDetections> = entries
+                .iter()
+                .map(|(input, labeled_detections)| AggregatedDetections {
+                    input: input.display().to_string(),
+                    detections: labeled_detections
+                        .iter()
+                        .map(
+                            |(_, (detection, prob, class_index, label))| DetectionRecord {
+                                label,
+                                confidence: *prob,
+                                bbox: detection.bbox().into(),
+                                class_index: *class_index,
+                            },
+                        )
+                        .collect(),
+                })
+                .collect();
+            Ok(serde_json::to_string(&aggregated)?)
+        }
+        OutputFormat::Csv => {
+            let mut output = String::from("input,label,confidence,x,y,w,h,class_index\n");
+            for (input, labeled_detections) in entries {
+                for (_, (detection, prob, class_index, label)) in *labeled_detections {
+                    let BBox { x, y, w, h } = detection.bbox();
+                    writeln!(
+                        output,
+                        "{},{},{},{},{},{},{},{}",
+                        csv_escape(&input.display().to_string()),
+                        csv_escape(label),
+                        prob,
+                        x,
+                        y,
+                        w,
+                        h,
+                        class_index,
+                    )?;
+                }
+            }
+            Ok(output)
+        }
+    }
 }
 
 impl DarknetInferenceService {
@@ -64,6 +388,10 @@ impl DarknetInferenceService {
             hierarchical_threshold: 0.0,
             iou_threshold: 0.0,
             letterbox: true,
+            annotated_output_path: None,
+            batch_input_paths: Vec::new(),
+            aggregate_batch_output: false,
+            output_format: OutputFormat::Text,
         }
     }
 
@@ -73,19 +401,101 @@ impl DarknetInferenceService {
     /// The input image is resized or letterboxed (depending on the `letterbox`
     /// parameter) before being fed to the model, which guarantees dimensions
     /// match.
+    ///
+    /// Two input shapes are accepted: the original postcard-encoded binary
+    /// blob, tried first, and a layered INI-style text config (see
+    /// [`config`]) as a fallback when the input isn't valid postcard. Once
+    /// the text is recognized as a config, a mapping failure (an invalid
+    /// value for a known key, e.g. a non-numeric threshold) is a hard error
+    /// rather
+    /// than a silent `Ok(false)`, since that is a malformed config rather
+    /// than an unrecognized input shape.
     fn try_parse(&mut self, input: &[u8]) -> anyhow::Result<bool> {
-        let deserialized_input: DarknetInferenceService = match postcard::from_bytes(&input) {
-            Ok(o) => o,
-            Err(_) => return Ok(false),
-        };
-        *self = deserialized_input;
-        Ok(true)
+        if let Ok(deserialized_input) = postcard::from_bytes::<DarknetInferenceService>(input) {
+            *self = deserialized_input;
+            return Ok(true);
+        }
+
+        if let Ok(text) = std::str::from_utf8(input) {
+            if let Ok(layered) = config::LayeredConfig::parse(text, Path::new("/")) {
+                *self = Self::from_layered_config(&layered)?;
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Resolve a [`config::LayeredConfig`] into a `DarknetInferenceService`,
+    /// starting from [`Self::new`]'s defaults and overlaying every key the
+    /// config resolves in its `[inference]` section. This lets a site keep
+    /// one "default thresholds" preset and `%include` it from per-job
+    /// configs that only override what differs.
+    fn from_layered_config(config: &config::LayeredConfig) -> anyhow::Result<Self> {
+        const SECTION: &str = "inference";
+        let mut service = Self::new();
+
+        if let Some(v) = config.get(SECTION, "input_path") {
+            service.input_path = PathBuf::from(v);
+        }
+        if let Some(v) = config.get(SECTION, "cfg_path") {
+            service.cfg_path = PathBuf::from(v);
+        }
+        if let Some(v) = config.get(SECTION, "model_path") {
+            service.model_path = PathBuf::from(v);
+        }
+        if let Some(v) = config.get(SECTION, "labels_path") {
+            service.labels_path = PathBuf::from(v);
+        }
+        if let Some(v) = config.get(SECTION, "output_path") {
+            service.output_path = PathBuf::from(v);
+        }
+        if let Some(v) = config.get(SECTION, "annotated_output_path") {
+            service.annotated_output_path = Some(PathBuf::from(v));
+        }
+        if let Some(v) = config.get(SECTION, "objectness_threshold") {
+            service.objectness_threshold = parse_threshold("objectness_threshold", v)?;
+        }
+        if let Some(v) = config.get(SECTION, "class_threshold") {
+            service.class_threshold = parse_threshold("class_threshold", v)?;
+        }
+        if let Some(v) = config.get(SECTION, "hierarchical_threshold") {
+            service.hierarchical_threshold = parse_threshold("hierarchical_threshold", v)?;
+        }
+        if let Some(v) = config.get(SECTION, "iou_threshold") {
+            service.iou_threshold = parse_threshold("iou_threshold", v)?;
+        }
+        if let Some(v) = config.get(SECTION, "letterbox") {
+            service.letterbox = parse_bool(v)?;
+        }
+        if let Some(v) = config.get(SECTION, "aggregate_batch_output") {
+            service.aggregate_batch_output = parse_bool(v)?;
+        }
+        if let Some(v) = config.get(SECTION, "batch_input_paths") {
+            service.batch_input_paths = v
+                .split(['\n', ','])
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from)
+                .collect();
+        }
+        if let Some(v) = config.get(SECTION, "output_format") {
+            service.output_format = match v.to_ascii_lowercase().as_str() {
+                "text" => OutputFormat::Text,
+                "json" => OutputFormat::Json,
+                "csv" => OutputFormat::Csv,
+                other => anyhow::bail!("unknown output_format: {:?}", other),
+            };
+        }
+
+        Ok(service)
     }
 
     /// The core service. It loads the model pointed by `model_path` with the
-    /// configuration in `cfg_path` and the labels defined in `labels_path`,
-    /// then feeds the input read from `input_path` to the model, and writes the
-    /// result to the file at `output_path`.
+    /// configuration in `cfg_path` and the labels defined in `labels_path`
+    /// exactly once, then feeds every input in the batch (`input_path` plus
+    /// `batch_input_paths`, or just `input_path` outside of batch mode)
+    /// through the model, writing results to `output_path`.
     fn infer(&mut self) -> anyhow::Result<()> {
         let DarknetInferenceService {
             input_path,
@@ -98,68 +508,173 @@ impl DarknetInferenceService {
             hierarchical_threshold,
             iou_threshold,
             letterbox,
+            annotated_output_path,
+            batch_input_paths,
+            aggregate_batch_output,
+            output_format,
         } = self;
 
-        // Load network and labels
-		println!("loading network...");
+        // Load network and labels once; this dominates runtime, so the cost
+        // is amortized across every input in the batch below.
+        //
+        // Cfg/weights consistency (e.g. a weights file built for a
+        // different architecture than `cfg_path` describes) is enforced by
+        // `Network::load` itself, not by this module: the `?` below
+        // surfaces whatever typed error the darknet binding returns rather
+        // than panicking. This module does not re-validate the binary
+        // weights format on top of that, since doing so would mean
+        // re-implementing darknet's own loader; [`validate_label_coverage`]
+        // below only checks that every class index actually detected in an
+        // input has a label, since it is this module's own indexing that
+        // would otherwise panic on it. A labels file shorter than the
+        // network's class count is not reported while none of its missing
+        // classes is detected, and a labels file longer than the class
+        // count is not reported at all.
+        println!("loading network...");
         let mut net = Network::load(cfg_path, Some(model_path), false)?;
-        let object_labels = read_to_string(labels_path)?
+        let object_labels = read_to_string(&*labels_path)?
             .lines()
             .map(ToOwned::to_owned)
             .collect::<Vec<_>>();
 
-        // Run inference
-        let image = Image::open(input_path)?;
-		println!("running inference on image...");
-        let detections = net.predict(
-            &image,
-            *objectness_threshold,
-            *hierarchical_threshold,
-            *iou_threshold,
-            *letterbox,
-        );
+        let inputs: Vec<&Path> = if batch_input_paths.is_empty() {
+            vec![input_path.as_path()]
+        } else {
+            std::iter::once(input_path.as_path())
+                .chain(batch_input_paths.iter().map(PathBuf::as_path))
+                .collect()
+        };
+        let is_batch = inputs.len() > 1;
+
+        let mut aggregated_entries: Vec<(&Path, Vec<LabeledDetection>)> = Vec::new();
+
+        for input in &inputs {
+            println!("running inference on {}...", input.display());
+            let image = Image::open(input)?;
+            let detections = net.predict(
+                &image,
+                *objectness_threshold,
+                *hierarchical_threshold,
+                *iou_threshold,
+                *letterbox,
+            );
 
-        // Apply class threshold and map detected objects to labels
-        let mut labeled_detections: Vec<(usize, (Detection, f32, &String))> = detections
-            .iter()
-            .flat_map(|det| {
-                det.best_class(Some(*class_threshold))
-                    .map(|(class_index, prob)| (det, prob, &object_labels[class_index]))
-            })
-            .enumerate()
-            .collect();
-
-        // Sort labeled detections by descending probability
-        labeled_detections.sort_by(|a, b| {
-            let (_, (_, prob_a, _)) = a;
-            let (_, (_, prob_b, _)) = b;
-            if prob_b > prob_a {
-                Ordering::Greater
-            } else if prob_b < prob_a {
-                Ordering::Less
+            // A detected class index past the end of the labels file would
+            // otherwise panic on the indexing below; surface it as a typed
+            // error instead.
+            let max_class_index = detections
+                .iter()
+                .flat_map(|det| det.best_class(Some(*class_threshold)).map(|(idx, _)| idx))
+                .max();
+            validate_label_coverage(object_labels.len(), max_class_index, labels_path)?;
+
+            // Apply class threshold and map detected objects to labels
+            let mut labeled_detections: Vec<LabeledDetection> = detections
+                .iter()
+                .flat_map(|det| {
+                    det.best_class(Some(*class_threshold))
+                        .map(|(class_index, prob)| {
+                            (det, prob, class_index, &object_labels[class_index])
+                        })
+                })
+                .enumerate()
+                .collect();
+
+            // Sort labeled detections by descending probability
+            labeled_detections.sort_by(|a, b| {
+                let (_, (_, prob_a, _, _)) = a;
+                let (_, (_, prob_b, _, _)) = b;
+                if prob_b > prob_a {
+                    Ordering::Greater
+                } else if prob_b < prob_a {
+                    Ordering::Less
+                } else {
+                    Ordering::Equal
+                }
+            });
+
+            // Draw surviving detections onto a clone of the input image, if requested.
+            //
+            // NOTE: this assumes `detection.bbox()` is normalized to `[0, 1]`
+            // and that `draw_box_width`/`put_label` expect *pixel*
+            // coordinates, so the box is scaled up by the image's own
+            // dimensions before drawing. Confirm both assumptions (including
+            // the exact `get_width`/`get_height` method names) against the
+            // `darknet` crate version actually pinned in this workspace
+            // before relying on this in production: if the drawing
+            // primitives already normalize internally (as darknet's own
+            // `draw_detections` does), this double-scales and boxes land
+            // off-image. The `clamp` below only guards against boxes
+            // landing outside the canvas; it does not paper over a wrong
+            // convention.
+            if let Some(annotated_output_path) = annotated_output_path {
+                let mut annotated = image.clone();
+                let width = annotated.get_width() as f32;
+                let height = annotated.get_height() as f32;
+                for (_, (detection, prob, class_index, label)) in &labeled_detections {
+                    let BBox { x, y, w, h } = detection.bbox();
+                    let pixel_bbox = BBox {
+                        x: (x * width).clamp(0.0, width),
+                        y: (y * height).clamp(0.0, height),
+                        w: (w * width).clamp(0.0, width),
+                        h: (h * height).clamp(0.0, height),
+                    };
+                    let color = class_color(*class_index);
+                    annotated.draw_box_width(pixel_bbox, 2, color);
+                    annotated.put_label(
+                        &format!("{} {:.2}%", label, prob * 100.0),
+                        pixel_bbox.x,
+                        pixel_bbox.y,
+                        color,
+                    );
+                }
+                let save_path = if is_batch {
+                    annotated_output_path.join(batch_file_name(input, "png"))
+                } else {
+                    annotated_output_path.clone()
+                };
+                println!("writing annotated image to {}...", save_path.display());
+                annotated.save(Path::new("/").join(save_path))?;
+            }
+
+            // Render this input's detections in the configured output format.
+            //
+            // The aggregated case defers rendering until every input has run:
+            // each format's aggregated document (one JSON array, one CSV
+            // table, one annotated text report) needs every input's
+            // detections at once to be keyed by input path correctly, rather
+            // than being built by concatenating each input's independent
+            // `render_output` call.
+            if is_batch && *aggregate_batch_output {
+                aggregated_entries.push((*input, labeled_detections));
+                continue;
+            }
+
+            let output = render_output(*output_format, &labeled_detections)?;
+
+            if !is_batch {
+                println!("writing results...");
+                let mut file = File::create(Path::new("/").join(&*output_path))?;
+                file.write_all(output.as_bytes())?;
             } else {
-                Ordering::Equal
+                let report_path =
+                    output_path.join(batch_file_name(input, output_extension(*output_format)));
+                println!("writing results to {}...", report_path.display());
+                let mut file = File::create(Path::new("/").join(report_path))?;
+                file.write_all(output.as_bytes())?;
             }
-        });
-
-        // Write result to output path
-        let mut output = String::new();
-        for (_, (detection, prob, label)) in labeled_detections {
-            let BBox { x, y, w, h } = detection.bbox();
-            write!(
-                output,
-                "{}\t{:.2}%\tx: {}\ty: {}\tw: {}\th: {}\n",
-                label,
-                prob * 100.0,
-                x,
-                y,
-                w,
-                h,
-            )?
-        }
-        println!("writing results...");
-        let mut file = File::create(Path::new("/").join(output_path))?;
-        file.write_all(&output.into_bytes())?;
+        }
+
+        if is_batch && *aggregate_batch_output {
+            println!("writing aggregated results...");
+            let entries: Vec<(&Path, &[LabeledDetection])> = aggregated_entries
+                .iter()
+                .map(|(input, detections)| (*input, detections.as_slice()))
+                .collect();
+            let aggregated_output = render_aggregated_output(*output_format, &entries)?;
+            let mut file = File::create(Path::new("/").join(&*output_path))?;
+            file.write_all(aggregated_output.as_bytes())?;
+        }
 
         Ok(())
     }
@@ -178,3 +693,115 @@ fn main() -> anyhow::Result<()> {
     service.try_parse(&input)?;
     service.infer()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_file_name_is_relative_for_an_absolute_input() {
+        let name = batch_file_name(Path::new("/images/img.jpg"), "txt");
+        assert!(
+            name.is_relative(),
+            "batch_file_name must return a relative path, got {name:?}"
+        );
+    }
+
+    #[test]
+    fn batch_report_path_lands_under_output_path() {
+        // `Path::join` silently discards its base when given an absolute
+        // argument, so an absolute `batch_file_name` would make every
+        // per-input report land at the filesystem root instead of under
+        // `output_path` as the batch mode intends.
+        let output_path = Path::new("/reports");
+        let report_path = output_path.join(batch_file_name(Path::new("/images/img.jpg"), "txt"));
+        assert!(
+            report_path.starts_with(output_path),
+            "expected {report_path:?} to live under {output_path:?}"
+        );
+    }
+
+    #[test]
+    fn batch_annotated_image_path_lands_under_annotated_output_path() {
+        // Same `Path::join`-discards-an-absolute-argument hazard as the
+        // per-input report path above, for the annotated-image save path.
+        let annotated_output_path = Path::new("/annotated");
+        let save_path =
+            annotated_output_path.join(batch_file_name(Path::new("/images/img.jpg"), "png"));
+        assert!(
+            save_path.starts_with(annotated_output_path),
+            "expected {save_path:?} to live under {annotated_output_path:?}"
+        );
+    }
+
+    #[test]
+    fn try_parse_rejects_truncated_postcard_without_panicking() {
+        let good = postcard::to_allocvec(&DarknetInferenceService::new()).unwrap();
+        let truncated = munger::truncate(&good, 0.5);
+        let mut service = DarknetInferenceService::new();
+        // Truncated postcard isn't valid UTF-8 config either, so this should
+        // fall through to `Ok(false)`, never panic or return a bogus struct.
+        let parsed = service.try_parse(&truncated).unwrap();
+        assert!(!parsed);
+    }
+
+    #[test]
+    fn try_parse_rejects_corrupted_bytes_without_panicking() {
+        let good = postcard::to_allocvec(&DarknetInferenceService::new()).unwrap();
+        let corrupted = munger::corrupt(&good);
+        let mut service = DarknetInferenceService::new();
+        // Bit-inverted bytes are neither valid postcard nor valid UTF-8, so
+        // this should fall through to `Ok(false)`.
+        assert!(!service.try_parse(&corrupted).unwrap());
+    }
+
+    #[test]
+    fn from_layered_config_rejects_nan_and_infinite_thresholds() {
+        for literal in munger::non_finite_threshold_literals() {
+            let text = format!("[inference]\nobjectness_threshold = {literal}\n");
+            let layered = config::LayeredConfig::parse(&text, Path::new("/")).unwrap();
+            let result = DarknetInferenceService::from_layered_config(&layered);
+            assert!(
+                result.is_err(),
+                "expected {literal:?} to be rejected as a threshold"
+            );
+        }
+    }
+
+    #[test]
+    fn from_layered_config_rejects_unknown_output_format() {
+        let text = "[inference]\noutput_format = yaml\n";
+        let layered = config::LayeredConfig::parse(text, Path::new("/")).unwrap();
+        assert!(DarknetInferenceService::from_layered_config(&layered).is_err());
+    }
+
+    #[test]
+    fn validate_label_coverage_accepts_empty_labels_with_no_detections() {
+        let empty = munger::empty_labels();
+        let labels: Vec<String> = empty.lines().map(ToOwned::to_owned).collect();
+        assert!(validate_label_coverage(labels.len(), None, Path::new("/labels.txt")).is_ok());
+    }
+
+    #[test]
+    fn validate_label_coverage_rejects_empty_labels_with_a_detection() {
+        let empty = munger::empty_labels();
+        let labels: Vec<String> = empty.lines().map(ToOwned::to_owned).collect();
+        assert!(validate_label_coverage(labels.len(), Some(0), Path::new("/labels.txt")).is_err());
+    }
+
+    #[test]
+    fn validate_label_coverage_accepts_oversized_labels() {
+        let oversized = munger::oversized_labels(10_000);
+        let labels: Vec<String> = oversized.lines().map(ToOwned::to_owned).collect();
+        assert!(
+            validate_label_coverage(labels.len(), Some(9_999), Path::new("/labels.txt")).is_ok()
+        );
+    }
+
+    #[test]
+    fn validate_label_coverage_rejects_class_index_past_labels_len() {
+        let oversized = munger::oversized_labels(10);
+        let labels: Vec<String> = oversized.lines().map(ToOwned::to_owned).collect();
+        assert!(validate_label_coverage(labels.len(), Some(10), Path::new("/labels.txt")).is_err());
+    }
+}
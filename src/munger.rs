@@ -0,0 +1,55 @@
+//! Fault-injection "munger" helpers, only compiled for tests.
+//!
+//! Named after directory-munging fault simulators: rather than hand-writing
+//! one broken fixture per bug, these take a known-good input and mechanically
+//! mangle it in ways real attackers or corrupted deployments would, so the
+//! same munger can be reused across every parsing entry point. Tests assert
+//! that mangled input is handled cleanly — rejected as `Ok(false)` by
+//! `try_parse` or with a typed `anyhow::Error` elsewhere, or accepted where
+//! it is still valid (an oversized labels file) — never a panic or undefined
+//! behavior.
+//!
+//! Scope: these are exercised against this module's own entry points
+//! (`try_parse`, `from_layered_config`, `validate_label_coverage`), not
+//! against `darknet::Image::open`/`darknet::Network::load` directly. Those
+//! two are FFI boundaries into the `darknet` C library, whose behavior on
+//! malformed image/weights bytes (clean `Result::Err`, vs. an abort/segfault
+//! that no amount of `catch_unwind` would catch) can't be verified without
+//! the real crate linked, which this tree does not have. Asserting `is_err()`
+//! there would assert a property this module does not control and cannot
+//! check here; revisit once the crate is available to build against.
+
+/// Truncate `bytes` to roughly `fraction` of their original length, as a
+/// stand-in for a weights/model file cut off mid-transfer.
+pub(crate) fn truncate(bytes: &[u8], fraction: f64) -> Vec<u8> {
+    let keep = ((bytes.len() as f64) * fraction) as usize;
+    bytes[..keep.min(bytes.len())].to_vec()
+}
+
+/// Invert every bit of every byte, as a stand-in for corrupted image bytes that
+/// still have a plausible length but garbage content.
+pub(crate) fn corrupt(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().map(|b| b ^ 0xff).collect()
+}
+
+/// An empty labels file: zero classes for a network that detects at least
+/// one.
+pub(crate) fn empty_labels() -> String {
+    String::new()
+}
+
+/// A labels file with far more entries than any real class count, as a
+/// stand-in for a labels file mismatched with the network in the other
+/// direction.
+pub(crate) fn oversized_labels(count: usize) -> String {
+    (0..count)
+        .map(|i| format!("label_{i}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Threshold spellings that parse as a float but are not usable as a
+/// probability threshold.
+pub(crate) fn non_finite_threshold_literals() -> &'static [&'static str] {
+    &["NaN", "nan", "inf", "-inf", "infinity"]
+}